@@ -27,10 +27,23 @@ pub fn get_pow_2(n: usize) -> Scalar {
     Scalar::from_bits(bits)
 }
 
+/*  return BASE^EXP, where EXP is a 256 bit integer stored
+    in little endian format. */
+pub fn scalar_pow(base: Scalar, exp: [u8; 32]) -> Scalar {
+    let mut result = Scalar::one();
+    for i in (0..256).rev() {
+        result = result * result;
+        if get_bit(exp, i) == 1 {
+            result *= base;
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod bit_helper_tests {
     use curve25519_dalek::scalar::Scalar;
-    use crate::bit_helpers::{get_bit, set_bit, sum_last_n_bits};
+    use crate::bit_helpers::{get_bit, scalar_pow, set_bit, sum_last_n_bits};
     use crate::get_pow_2;
 
     #[test]
@@ -156,4 +169,15 @@ mod bit_helper_tests {
             power = power * two;
         }
     }
+
+    #[test]
+    fn scalar_pow_test() {
+        let base: Scalar = Scalar::from(3u32);
+        assert_eq!(scalar_pow(base, [0u8; 32]), Scalar::one());
+        assert_eq!(scalar_pow(base, Scalar::from(5u32).to_bytes()), Scalar::from(243u32));
+        assert_eq!(scalar_pow(Scalar::zero(), Scalar::from(5u32).to_bytes()), Scalar::zero());
+
+        // Fermat: base^(l - 1) == 1
+        assert_eq!(scalar_pow(base, (-Scalar::one()).to_bytes()), Scalar::one());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+extern crate curve25519_dalek;
+
+use std::cmp::{max, min};
+use std::ops::Range;
+use curve25519_dalek::scalar::Scalar;
+use crate::r1cs::R1CS;
+
+/*  Common interface of the algebraic hash functions (Poseidon, MiMC-Feistel, Rescue-Prime),
+    so gadgets such as Merkle membership can be built with any of them and the hashes can be
+    compared on constraint count and prover time. For every implementation the gadget output
+    must equal the native output. */
+pub trait HashGadget {
+    /* Short name of the hash, used when reporting benchmarks. */
+    fn name(&self) -> &'static str;
+
+    /* Natively hash a fixed length list of INPUTS. */
+    fn hash(&self, inputs: &[Scalar]) -> Scalar;
+
+    /* Natively hash two field elements, e.g. the children of a Merkle tree node. */
+    fn compress(&self, x: Scalar, y: Scalar) -> Scalar {
+        self.hash(&[x, y])
+    }
+
+    /* Add constraints so that OUT == hash(INPUTS). Intermediate variables are named after OUT. */
+    fn new_hash_constraint(&self, r1cs: &mut R1CS, inputs: &[&str], out: &str);
+
+    /* Assign the witness of the intermediate variables and OUT, returning the value of OUT. */
+    fn generate_witness_hash(&self, r1cs: &mut R1CS, inputs: &[Scalar], out: &str) -> Scalar;
+}
+
+/*  The width x width Cauchy matrix M[i][j] = 1 / (x_i + y_j) with x_i = i and y_j = width + j.
+    It is MDS because all x_i and y_j are distinct and no x_i + y_j is zero. */
+pub fn cauchy_mds_matrix(width: usize) -> Vec<Vec<Scalar>> {
+    (0..width)
+        .map(|i| (0..width).map(|j| Scalar::from((i + width + j) as u64).invert()).collect())
+        .collect()
+}
+
+/*  Split LEN sponge inputs into blocks of RATE, returning the input indices absorbed by each
+    permutation call. The last block may be short (it is padded with zeroes) and there is
+    always at least one block so the empty input is still mixed. */
+pub fn sponge_blocks(len: usize, rate: usize) -> Vec<Range<usize>> {
+    let num_blocks = max(1, len.div_ceil(rate));
+    (0..num_blocks).map(|b| (b * rate)..min(len, (b + 1) * rate)).collect()
+}
+
+impl R1CS {
+    /* Add constraints so that OUT == HASHER.hash(INPUTS). */
+    pub fn new_hash_constraint<H: HashGadget>(&mut self, hasher: &H, inputs: &[&str], out: &str) {
+        hasher.new_hash_constraint(self, inputs, out);
+    }
+
+    pub fn generate_witness_hash<H: HashGadget>(&mut self, hasher: &H, inputs: &[Scalar], out: &str) -> Scalar {
+        hasher.generate_witness_hash(self, inputs, out)
+    }
+}
+
+#[cfg(test)]
+mod hash_tests {
+    use curve25519_dalek::scalar::Scalar;
+    use crate::hash::HashGadget;
+    use crate::mimc::MiMC;
+    use crate::poseidon::Poseidon;
+    use crate::r1cs::R1CS;
+    use crate::rescue::RescuePrime;
+
+    /* Returns the gadget output, whether the witness satisfies the instance and the constraint count. */
+    fn hash_gadget_test_helper<H: HashGadget>(hasher: &H, inputs: &[Scalar]) -> (Scalar, bool, usize) {
+        let names: Vec<String> = (0..inputs.len()).map(|i| format!("in[{}]", i)).collect();
+        let mut r1cs: R1CS = R1CS::new(&Vec::new());
+        r1cs.new_hash_constraint(hasher, &names.iter().map(|s| s.as_str()).collect::<Vec<&str>>(), "h");
+        for (name, value) in names.iter().zip(inputs.iter()) {
+            r1cs.add_witness_var_assignment(name, *value);
+        }
+        let h = r1cs.generate_witness_hash(hasher, inputs, "h");
+        let (inst, num_cons, _, _, _) = r1cs.build_instance();
+        let (assignment_vars, assignment_inputs) = r1cs.build_witness();
+        (h, inst.is_sat(&assignment_vars, &assignment_inputs).unwrap_or(false), num_cons)
+    }
+
+    fn hash_gadget_matches_native<H: HashGadget>(hasher: &H) {
+        let input_lists: Vec<Vec<Scalar>> = Vec::from([
+            Vec::new(),
+            Vec::from([Scalar::from(9u32)]),
+            Vec::from([Scalar::one(), Scalar::from(2u32)]),
+            Vec::from([-Scalar::one(), Scalar::from(5u32), Scalar::from(123456789u64)]),
+        ]);
+        for inputs in input_lists {
+            let (h, sat, _) = hash_gadget_test_helper(hasher, &inputs);
+            assert!(sat, "testing {} witness satisfies instance", hasher.name());
+            assert_eq!(h, hasher.hash(&inputs), "testing {} gadget output", hasher.name());
+        }
+        assert_ne!(hasher.compress(Scalar::one(), Scalar::zero()), hasher.compress(Scalar::zero(), Scalar::one()));
+    }
+
+    #[test]
+    fn poseidon_hash_gadget_test() {
+        hash_gadget_matches_native(&Poseidon::new());
+    }
+
+    #[test]
+    fn mimc_hash_gadget_test() {
+        hash_gadget_matches_native(&MiMC::new());
+    }
+
+    #[test]
+    fn rescue_hash_gadget_test() {
+        hash_gadget_matches_native(&RescuePrime::new());
+    }
+
+    #[test]
+    fn hash_gadget_constraint_count_test() {
+        let inputs = [Scalar::one(), Scalar::from(2u32)];
+        /* one extra constraint for the output and one for the "one" variable */
+        assert_eq!(hash_gadget_test_helper(&Poseidon::new(), &inputs).2, 3 * (8 * 3 + 57) + 2);
+        assert_eq!(hash_gadget_test_helper(&MiMC::new(), &inputs).2, 2 * 3 * 220 + 2);
+        assert_eq!(hash_gadget_test_helper(&RescuePrime::new(), &inputs).2, 2 * 3 * 3 * 14 + 2);
+    }
+}
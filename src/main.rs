@@ -4,6 +4,11 @@ pub mod r1cs;
 pub mod bit_helpers;
 pub mod range_proof;
 pub mod set_membership;
+pub mod hash;
+pub mod mimc;
+pub mod rescue;
+pub mod poseidon;
+pub mod merkle;
 
 extern crate curve25519_dalek;
 extern crate libspartan;
@@ -0,0 +1,296 @@
+extern crate curve25519_dalek;
+extern crate libspartan;
+extern crate merlin;
+
+use curve25519_dalek::scalar::Scalar;
+use libspartan::{InputsAssignment, Instance, VarsAssignment};
+use merlin::Transcript;
+use crate::hash::HashGadget;
+use crate::mimc::MiMC;
+use crate::r1cs::*;
+
+/*  A binary Merkle tree over scalar leaves, where each node is the compression of its two
+    children under a HashGadget (MiMC unless built with with_hasher). The leaves are padded
+    with padding_leaf up to the next power of two. layers[0] holds the leaves and the last layer
+    holds the root. */
+pub struct MerkleTree {
+    depth: usize,
+    layers: Vec<Vec<Scalar>>
+}
+
+impl MerkleTree {
+    pub fn new(leaves: &[Scalar]) -> MerkleTree {
+        MerkleTree::with_hasher(&MiMC::new(), leaves)
+    }
+
+    pub fn with_hasher<H: HashGadget>(hasher: &H, leaves: &[Scalar]) -> MerkleTree {
+        assert!(!leaves.is_empty());
+
+        let mut depth = 0;
+        while (1 << depth) < leaves.len() {
+            depth += 1;
+        }
+
+        let mut layer: Vec<Scalar> = leaves.to_vec();
+        layer.resize(1 << depth, padding_leaf());
+
+        let mut layers: Vec<Vec<Scalar>> = Vec::from([layer]);
+        for _ in 0..depth {
+            let next: Vec<Scalar> = layers.last().unwrap()
+                .chunks(2)
+                .map(|pair| hasher.compress(pair[0], pair[1]))
+                .collect();
+            layers.push(next);
+        }
+
+        MerkleTree { depth, layers }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn root(&self) -> Scalar {
+        self.layers[self.depth][0]
+    }
+
+    /* Return the siblings of the path from leaf INDEX to the root, starting at the leaf level. */
+    pub fn auth_path(&self, index: usize) -> Vec<Scalar> {
+        assert!(index < (1 << self.depth));
+        (0..self.depth).map(|level| self.layers[level][(index >> level) ^ 1]).collect()
+    }
+}
+
+/*  The leaf that pads a tree up to a power of two: a fixed scalar derived from a tag. The
+    membership gadget does not bound the position of the leaf, so anything stored at a padding
+    position can be proven to be a member. Unlike zero, this is not a value a secret would hold. */
+pub fn padding_leaf() -> Scalar {
+    let mut transcript = Transcript::new(b"Merkle tree padding leaf");
+    let mut buf = [0u8; 64];
+    transcript.challenge_bytes(b"tag", &mut buf);
+    Scalar::from_bytes_mod_order_wide(&buf)
+}
+
+/* Recompute the root from LEAF at position INDEX using the authentication path PATH. */
+pub fn merkle_root_from_path<H: HashGadget>(hasher: &H, leaf: Scalar, index: usize, path: &[Scalar]) -> Scalar {
+    let mut node = leaf;
+    for (level, sibling) in path.iter().enumerate() {
+        node = if (index >> level) & 1 == 0 {
+            hasher.compress(node, *sibling)
+        } else {
+            hasher.compress(*sibling, node)
+        };
+    }
+    node
+}
+
+impl R1CS {
+    /*  Add constraints to verify that LEAF is a leaf of a depth DEPTH Merkle tree with root ROOT.
+        The authentication path and the position of the leaf are private witness variables, so
+        the cost is DEPTH hash evaluations regardless of the number of leaves. */
+    pub fn new_merkle_membership_constraint<H: HashGadget>(&mut self, hasher: &H, leaf: &str, root: &str, depth: usize) {
+        let one = Scalar::one();
+        let mut node: String = leaf.to_string();
+        for i in 0..depth {
+            let sibling = format!("{}_merkle_path[{}]", leaf, i);
+            let dir = format!("{}_merkle_dir[{}]", leaf, i);
+            let left = format!("{}_merkle_left[{}]", leaf, i);
+            let right = format!("{}_merkle_right[{}]", leaf, i);
+            let next = format!("{}_merkle_node[{}]", leaf, i + 1);
+
+            /* dir is 0 when the current node is a left child, 1 when it is a right child */
+            self.new_is_bit_constraint(&dir);
+
+            /* dir * (sibling - node) == left - node, so left is node when dir = 0 and sibling when dir = 1 */
+            self.new_lc_constraint(&[(&dir, one)], &[(&sibling, one), (&node, -one)], &[(&left, one), (&node, -one)]);
+
+            /* right is whichever of node and sibling is not left */
+            self.new_lc_constraint(&[(&node, one), (&sibling, one), (&left, -one)], &[("one", one)], &[(&right, one)]);
+
+            self.new_hash_constraint(hasher, &[&left, &right], &next);
+            node = next;
+        }
+        self.new_equality_constraint(&node, root);
+    }
+
+    pub fn generate_witness_merkle_membership<H: HashGadget>(&mut self, hasher: &H, leaf: &str, leaf_val: Scalar, index: usize, path: &[Scalar]) {
+        let mut node = leaf_val;
+        for (i, sibling) in path.iter().enumerate() {
+            let is_right = (index >> i) & 1 == 1;
+            let (left, right) = if is_right { (*sibling, node) } else { (node, *sibling) };
+
+            self.add_witness_var_assignment(&format!("{}_merkle_path[{}]", leaf, i), *sibling);
+            self.add_witness_var_assignment(&format!("{}_merkle_dir[{}]", leaf, i), if is_right { Scalar::one() } else { Scalar::zero() });
+            self.add_witness_var_assignment(&format!("{}_merkle_left[{}]", leaf, i), left);
+            self.add_witness_var_assignment(&format!("{}_merkle_right[{}]", leaf, i), right);
+            node = self.generate_witness_hash(hasher, &[left, right], &format!("{}_merkle_node[{}]", leaf, i + 1));
+        }
+    }
+}
+
+/*  Generates a R1CS instance for a proof that SECRET is the leaf at position INDEX of the
+    Merkle tree with root ROOT, given the authentication path PATH. Only ROOT is public. */
+pub fn produce_merkle_membership_r1cs(secret: Scalar, index: usize, path: Vec<Scalar>, root: Scalar) -> (
+    usize,
+    usize,
+    usize,
+    usize,
+    Instance,
+    VarsAssignment,
+    InputsAssignment,
+    bool
+) {
+    /* Build a rank 1 constraint system so that SECRET is a leaf of the tree with root ROOT. */
+    let mimc = MiMC::new();
+    let mut r1cs: R1CS = R1CS::new(&Vec::from([("root", root)]));
+
+    // generate constraints for a merkle membership proof
+    r1cs.new_merkle_membership_constraint(&mimc, "secret", "root", path.len());
+
+    // generate a witness to satisfy these constraints
+    r1cs.add_witness_var_assignment("secret", secret);
+    r1cs.generate_witness_merkle_membership(&mimc, "secret", secret, index, &path);
+
+    // build our r1cs instance
+    let (inst, num_cons, num_vars, num_inputs, num_non_zero_entries) = r1cs.build_instance();
+
+    // build our witness
+    let (assignment_vars, assignment_inputs) = r1cs.build_witness();
+
+    // check if the instance we created is satisfied by our witness
+    let witness_satisfies_instance: bool = inst
+        .is_sat(&assignment_vars, &assignment_inputs)
+        .unwrap_or(false);
+
+    (
+        num_cons,
+        num_vars,
+        num_inputs,
+        num_non_zero_entries,
+        inst,
+        assignment_vars,
+        assignment_inputs,
+        witness_satisfies_instance
+    )
+}
+
+#[cfg(test)]
+mod merkle_tests {
+    use curve25519_dalek::scalar::Scalar;
+    use libspartan::{InputsAssignment, SNARK, SNARKGens};
+    use merlin::Transcript;
+    use crate::merkle::{merkle_root_from_path, produce_merkle_membership_r1cs, MerkleTree};
+    use crate::mimc::MiMC;
+    use crate::poseidon::Poseidon;
+
+    fn merkle_membership_test_helper(secret: Scalar, index: usize, path: Vec<Scalar>, root: Scalar, expected_to_verify: bool) {
+        // produce a merkle membership proof
+        let (
+            num_cons,
+            num_vars,
+            num_inputs,
+            num_non_zero_entries,
+            inst,
+            assignment_vars,
+            assignment_inputs,
+            _
+        ) = produce_merkle_membership_r1cs(secret, index, path, root);
+
+        // produce public parameters
+        let gens = SNARKGens::new(num_cons, num_vars, num_inputs, num_non_zero_entries);
+
+        // create a commitment to the R1CS instance
+        let (comm, decomm) = SNARK::encode(&inst, &gens);
+
+        // produce a proof of satisfiability
+        let mut prover_transcript = Transcript::new(b"merkle_membership_test");
+        let proof = SNARK::prove(
+            &inst,
+            &decomm,
+            assignment_vars,
+            &assignment_inputs,
+            &gens,
+            &mut prover_transcript,
+        );
+
+        // generate inputs, if any
+        let custom_inputs = vec![Scalar::zero().to_bytes(); num_inputs];
+        let custom_assignment_inputs = InputsAssignment::new(&custom_inputs).unwrap();
+
+        // verify the proof of satisfiability
+        let mut verifier_transcript = Transcript::new(b"merkle_membership_test");
+        assert_eq!(
+            expected_to_verify,
+            proof
+                .verify(&comm, &custom_assignment_inputs, &mut verifier_transcript, &gens)
+                .is_ok(),
+            "testing merkle membership proof"
+        );
+    }
+
+    fn small_leaves() -> Vec<Scalar> {
+        Vec::from([
+            Scalar::from(1323u32),
+            Scalar::from(3u32),
+            Scalar::from(0u32),
+            Scalar::from(4235u32),
+            Scalar::from(13532u32),
+        ])
+    }
+
+    #[test]
+    fn merkle_tree_native_path_test() {
+        let leaves = small_leaves();
+        let tree = MerkleTree::new(&leaves);
+        assert_eq!(tree.depth(), 3);
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(merkle_root_from_path(&MiMC::new(), *leaf, i, &tree.auth_path(i)), tree.root(), "testing leaf {}", i);
+        }
+        assert_ne!(merkle_root_from_path(&MiMC::new(), Scalar::from(5u32), 0, &tree.auth_path(0)), tree.root());
+    }
+
+    #[test]
+    fn merkle_tree_with_hasher_test() {
+        let leaves = small_leaves();
+        let poseidon = Poseidon::new();
+        let tree = MerkleTree::with_hasher(&poseidon, &leaves);
+        assert_ne!(tree.root(), MerkleTree::new(&leaves).root());
+        assert_eq!(merkle_root_from_path(&poseidon, leaves[3], 3, &tree.auth_path(3)), tree.root());
+    }
+
+    #[test]
+    fn merkle_tree_singleton_test() {
+        let tree = MerkleTree::new(&[Scalar::from(7u32)]);
+        assert_eq!(tree.depth(), 0);
+        assert_eq!(tree.root(), Scalar::from(7u32));
+        merkle_membership_test_helper(Scalar::from(7u32), 0, tree.auth_path(0), tree.root(), true);
+        merkle_membership_test_helper(Scalar::from(8u32), 0, tree.auth_path(0), tree.root(), false);
+    }
+
+    #[test]
+    fn merkle_membership_is_member_test() {
+        let leaves = small_leaves();
+        let tree = MerkleTree::new(&leaves);
+        for (i, leaf) in leaves.iter().enumerate() {
+            merkle_membership_test_helper(*leaf, i, tree.auth_path(i), tree.root(), true);
+        }
+    }
+
+    #[test]
+    fn merkle_membership_is_not_member_test() {
+        let leaves = small_leaves();
+        let tree = MerkleTree::new(&leaves);
+        merkle_membership_test_helper(Scalar::from(4u32), 1, tree.auth_path(1), tree.root(), false);
+        merkle_membership_test_helper(Scalar::from(1323u32), 1, tree.auth_path(1), tree.root(), false);
+        merkle_membership_test_helper(Scalar::from(1323u32), 0, tree.auth_path(0), Scalar::from(1u32), false);
+    }
+
+    #[test]
+    fn merkle_membership_padding_leaf_test() {
+        let leaves = small_leaves();
+        let tree = MerkleTree::new(&leaves);
+        for i in leaves.len()..(1 << tree.depth()) {
+            merkle_membership_test_helper(Scalar::zero(), i, tree.auth_path(i), tree.root(), false);
+        }
+    }
+}
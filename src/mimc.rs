@@ -0,0 +1,177 @@
+extern crate curve25519_dalek;
+extern crate merlin;
+
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use crate::hash::HashGadget;
+use crate::r1cs::{LinearCombination, R1CS};
+
+/*  Number of Feistel rounds. With exponent 5 over a ~253 bit field, MiMC needs
+    ceil(log_5(l)) = 109 rounds per branch, so the Feistel variant needs twice that. We use
+    220 rounds, the same count used for the similarly sized BN254 field. */
+pub const MIMC_ROUNDS: usize = 220;
+
+/*  MiMC-Feistel permutation over the curve25519 scalar field with round function
+    (xL, xR) -> (xR + (xL + c_i)^5, xL). x^5 is a permutation because gcd(5, l - 1) = 1.
+
+    The round constants c_i are produced by a merlin transcript labelled
+    "MiMC-Feistel-5 curve25519 scalar field": for each round we squeeze 64 bytes under the
+    label "round constant" and reduce them mod l. Anyone with merlin can recompute them. */
+pub struct MiMC {
+    round_constants: Vec<Scalar>
+}
+
+impl MiMC {
+    pub fn new() -> MiMC {
+        let mut transcript = Transcript::new(b"MiMC-Feistel-5 curve25519 scalar field");
+        let round_constants = (0..MIMC_ROUNDS).map(|_| {
+            let mut buf = [0u8; 64];
+            transcript.challenge_bytes(b"round constant", &mut buf);
+            Scalar::from_bytes_mod_order_wide(&buf)
+        }).collect();
+        MiMC { round_constants }
+    }
+
+    /* Apply the Feistel permutation to the state (XL, XR). */
+    pub fn permute(&self, xl: Scalar, xr: Scalar) -> (Scalar, Scalar) {
+        let (mut xl, mut xr) = (xl, xr);
+        for c in self.round_constants.iter() {
+            let u = xl + c;
+            let u2 = u * u;
+            let new_xl = xr + u2 * u2 * u;
+            xr = xl;
+            xl = new_xl;
+        }
+        (xl, xr)
+    }
+
+    /*  Number of permutation calls when hashing LEN inputs. The sponge has rate 1, so one call
+        per input, and at least one call so the empty input is still mixed. */
+    fn num_blocks(len: usize) -> usize {
+        std::cmp::max(1, len)
+    }
+}
+
+impl Default for MiMC {
+    fn default() -> MiMC {
+        MiMC::new()
+    }
+}
+
+impl HashGadget for MiMC {
+    fn name(&self) -> &'static str {
+        "MiMC-Feistel"
+    }
+
+    /*  Sponge with rate 1 and capacity 1: the capacity branch xR starts at the input length
+        for domain separation, each input is added to xL followed by a permutation, and the
+        final xL is squeezed. */
+    fn hash(&self, inputs: &[Scalar]) -> Scalar {
+        let (mut xl, mut xr) = (Scalar::zero(), Scalar::from(inputs.len() as u64));
+        for b in 0..MiMC::num_blocks(inputs.len()) {
+            let x = inputs.get(b).copied().unwrap_or(Scalar::zero());
+            (xl, xr) = self.permute(xl + x, xr);
+        }
+        xl
+    }
+
+    fn new_hash_constraint(&self, r1cs: &mut R1CS, inputs: &[&str], out: &str) {
+        let one = Scalar::one();
+        let mut xl = LinearCombination::zero();
+        let mut xr = LinearCombination::from_scalar(Scalar::from(inputs.len() as u64));
+        for b in 0..MiMC::num_blocks(inputs.len()) {
+            if let Some(x) = inputs.get(b) {
+                xl.add_term(x, one);
+            }
+            (xl, xr) = r1cs.mimc_permutation_lc(self, &format!("{}_block[{}]", out, b), xl, xr);
+        }
+        r1cs.new_lc_constraint(&xl.terms(), &[("one", one)], &[(out, one)]);
+    }
+
+    fn generate_witness_hash(&self, r1cs: &mut R1CS, inputs: &[Scalar], out: &str) -> Scalar {
+        let (mut xl, mut xr) = (Scalar::zero(), Scalar::from(inputs.len() as u64));
+        for b in 0..MiMC::num_blocks(inputs.len()) {
+            let x = inputs.get(b).copied().unwrap_or(Scalar::zero());
+            (xl, xr) = r1cs.generate_witness_mimc_permutation_lc(self, &format!("{}_block[{}]", out, b), xl + x, xr);
+        }
+        r1cs.add_witness_var_assignment(out, xl);
+        xl
+    }
+}
+
+impl R1CS {
+    /*  Add constraints applying the MiMC-Feistel permutation to (XL, XR) and return the new
+        state. Each round costs 3 constraints: u^2, u^4 and xR + u^5 where u = xL + c_i. */
+    fn mimc_permutation_lc(&mut self, mimc: &MiMC, prefix: &str, xl: LinearCombination, xr: LinearCombination) -> (LinearCombination, LinearCombination) {
+        let one = Scalar::one();
+        let (mut xl, mut xr) = (xl, xr);
+        for (i, c) in mimc.round_constants.iter().enumerate() {
+            let u2 = format!("{}_mimc_u2[{}]", prefix, i);
+            let u4 = format!("{}_mimc_u4[{}]", prefix, i);
+            let new_xl = format!("{}_mimc_l[{}]", prefix, i + 1);
+
+            /* u2 = u^2, u4 = u2^2, u4 * u = new_xl - xr */
+            let mut u = xl.clone();
+            u.add_term("one", *c);
+            self.new_lc_constraint(&u.terms(), &u.terms(), &[(&u2, one)]);
+            self.new_lc_constraint(&[(&u2, one)], &[(&u2, one)], &[(&u4, one)]);
+            let c_terms = LinearCombination::from_var(&new_xl).add_scaled(&xr, -one);
+            self.new_lc_constraint(&[(&u4, one)], &u.terms(), &c_terms.terms());
+
+            xr = xl;
+            xl = LinearCombination::from_var(&new_xl);
+        }
+        (xl, xr)
+    }
+
+    fn generate_witness_mimc_permutation_lc(&mut self, mimc: &MiMC, prefix: &str, xl: Scalar, xr: Scalar) -> (Scalar, Scalar) {
+        let (mut xl, mut xr) = (xl, xr);
+        for (i, c) in mimc.round_constants.iter().enumerate() {
+            let u = xl + c;
+            let u2 = u * u;
+            let u4 = u2 * u2;
+            let new_xl = xr + u4 * u;
+            self.add_witness_var_assignment(&format!("{}_mimc_u2[{}]", prefix, i), u2);
+            self.add_witness_var_assignment(&format!("{}_mimc_u4[{}]", prefix, i), u4);
+            self.add_witness_var_assignment(&format!("{}_mimc_l[{}]", prefix, i + 1), new_xl);
+            xr = xl;
+            xl = new_xl;
+        }
+        (xl, xr)
+    }
+}
+
+#[cfg(test)]
+mod mimc_tests {
+    use curve25519_dalek::scalar::Scalar;
+    use crate::hash::HashGadget;
+    use crate::mimc::MiMC;
+
+    #[test]
+    fn mimc_constants_are_deterministic_test() {
+        assert!(MiMC::new().round_constants == MiMC::new().round_constants);
+    }
+
+    #[test]
+    fn mimc_permutation_is_invertible_test() {
+        /* undo the rounds in reverse order: xL = xR' and xR = xL' - (xR' + c_i)^5 */
+        let mimc = MiMC::new();
+        let (xl, xr) = (Scalar::from(3u32), Scalar::from(4u32));
+        let (mut l, mut r) = mimc.permute(xl, xr);
+        for c in mimc.round_constants.iter().rev() {
+            let u = r + c;
+            let u2 = u * u;
+            let prev_r = l - u2 * u2 * u;
+            l = r;
+            r = prev_r;
+        }
+        assert_eq!((l, r), (xl, xr));
+    }
+
+    #[test]
+    fn mimc_hash_is_length_separated_test() {
+        let mimc = MiMC::new();
+        assert_ne!(mimc.hash(&[Scalar::one()]), mimc.hash(&[Scalar::one(), Scalar::zero()]));
+        assert_ne!(mimc.hash(&[]), mimc.hash(&[Scalar::zero()]));
+    }
+}
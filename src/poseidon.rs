@@ -0,0 +1,328 @@
+extern crate curve25519_dalek;
+
+use curve25519_dalek::scalar::Scalar;
+use crate::bit_helpers::set_bit;
+use crate::hash::{cauchy_mds_matrix, sponge_blocks, HashGadget};
+use crate::r1cs::{LinearCombination, R1CS};
+
+/* Bit length of the curve25519 scalar field modulus l = 2^252 + 27742317777372353535851937790883648493. */
+pub const SCALAR_FIELD_BITS: usize = 253;
+
+/*  Default Poseidon parameters: width 3 (rate 2, capacity 1), S-box x^5 and 8 full plus
+    57 partial rounds, the 128 bit security round numbers from the Poseidon paper for
+    ~255 bit fields. x^5 is a permutation of the scalar field because gcd(5, l - 1) = 1. */
+pub const POSEIDON_WIDTH: usize = 3;
+pub const POSEIDON_FULL_ROUNDS: usize = 8;
+pub const POSEIDON_PARTIAL_ROUNDS: usize = 57;
+
+/*  The Grain LFSR used by the Poseidon reference implementation (generate_parameters_grain.sage)
+    to derive round constants. The 80 bit state is seeded with the field type (2 bits, 1 for a
+    prime field), the S-box type (4 bits, 0 for x^alpha), the field size, the width, the number
+    of full rounds and the number of partial rounds (12, 12, 10 and 10 bits, most significant
+    bit first), followed by 30 one bits. The first 160 output bits are discarded. */
+struct GrainLFSR {
+    state: Vec<bool>
+}
+
+impl GrainLFSR {
+    fn new(field_size: usize, width: usize, full_rounds: usize, partial_rounds: usize) -> GrainLFSR {
+        let mut state: Vec<bool> = Vec::with_capacity(80);
+        let mut push_bits = |value: usize, n: usize| {
+            for i in (0..n).rev() {
+                state.push((value >> i) & 1 == 1);
+            }
+        };
+        push_bits(1, 2);
+        push_bits(0, 4);
+        push_bits(field_size, 12);
+        push_bits(width, 12);
+        push_bits(full_rounds, 10);
+        push_bits(partial_rounds, 10);
+        push_bits((1 << 30) - 1, 30);
+
+        let mut lfsr = GrainLFSR { state };
+        for _ in 0..160 {
+            lfsr.next_bit();
+        }
+        lfsr
+    }
+
+    fn next_bit(&mut self) -> bool {
+        let s = &self.state;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.remove(0);
+        self.state.push(bit);
+        bit
+    }
+
+    /* Output bits come in pairs: the second bit is kept only if the first bit is 1. */
+    fn next_filtered_bit(&mut self) -> bool {
+        loop {
+            let keep = self.next_bit();
+            let bit = self.next_bit();
+            if keep {
+                return bit;
+            }
+        }
+    }
+
+    /* Read SCALAR_FIELD_BITS bits, most significant first, rejecting values >= l. */
+    fn next_scalar(&mut self) -> Scalar {
+        loop {
+            let mut bits = [0u8; 32];
+            for i in (0..SCALAR_FIELD_BITS).rev() {
+                set_bit(&mut bits, i, self.next_filtered_bit() as u8);
+            }
+            if let Some(value) = Scalar::from_canonical_bytes(bits) {
+                return value;
+            }
+        }
+    }
+}
+
+/*  The Poseidon permutation and a sponge built from it, over the curve25519 scalar field.
+
+    Round constants come from GrainLFSR in the order they are used (round by round, element
+    by element). The MDS matrix is cauchy_mds_matrix(width).
+
+    These parameters are not interoperable with standard Poseidon, and the hashes do not match
+    other implementations. The reference script also draws the MDS matrix from the Grain LFSR
+    and rejects matrices with invariant subspace trails; this fixed matrix has not been checked
+    for them. */
+pub struct Poseidon {
+    width: usize,
+    full_rounds: usize,
+    partial_rounds: usize,
+    round_constants: Vec<Scalar>,
+    mds: Vec<Vec<Scalar>>
+}
+
+impl Poseidon {
+    pub fn new() -> Poseidon {
+        Poseidon::with_params(POSEIDON_WIDTH, POSEIDON_FULL_ROUNDS, POSEIDON_PARTIAL_ROUNDS)
+    }
+
+    pub fn with_params(width: usize, full_rounds: usize, partial_rounds: usize) -> Poseidon {
+        assert!(width >= 2);
+        assert_eq!(full_rounds % 2, 0);
+        let mut lfsr = GrainLFSR::new(SCALAR_FIELD_BITS, width, full_rounds, partial_rounds);
+        let round_constants = (0..(full_rounds + partial_rounds) * width)
+            .map(|_| lfsr.next_scalar())
+            .collect();
+        let mds = cauchy_mds_matrix(width);
+        Poseidon { width, full_rounds, partial_rounds, round_constants, mds }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn rate(&self) -> usize {
+        self.width - 1
+    }
+
+    fn is_full_round(&self, round: usize) -> bool {
+        round < self.full_rounds / 2 || round >= self.full_rounds / 2 + self.partial_rounds
+    }
+
+    /* Apply the permutation to STATE, which must have WIDTH elements. */
+    pub fn permute(&self, state: &[Scalar]) -> Vec<Scalar> {
+        assert_eq!(state.len(), self.width);
+        let mut state: Vec<Scalar> = state.to_vec();
+        for round in 0..self.full_rounds + self.partial_rounds {
+            for (x, c) in state.iter_mut().zip(self.round_constants[round * self.width..].iter()) {
+                *x += c;
+            }
+            let sbox_count = if self.is_full_round(round) { self.width } else { 1 };
+            for x in state.iter_mut().take(sbox_count) {
+                let x2 = *x * *x;
+                *x = x2 * x2 * *x;
+            }
+            state = self.mds.iter()
+                .map(|row| row.iter().zip(state.iter()).map(|(m, x)| m * x).sum())
+                .collect();
+        }
+        state
+    }
+}
+
+impl Default for Poseidon {
+    fn default() -> Poseidon {
+        Poseidon::new()
+    }
+}
+
+impl HashGadget for Poseidon {
+    fn name(&self) -> &'static str {
+        "Poseidon"
+    }
+
+    /*  Hash a fixed length list of INPUTS. The capacity element (state[0]) is initialised with
+        the input length for domain separation, inputs are absorbed RATE at a time into the
+        remaining elements (the last block padded with zeroes) and state[1] is squeezed. */
+    fn hash(&self, inputs: &[Scalar]) -> Scalar {
+        let mut state = vec![Scalar::zero(); self.width];
+        state[0] = Scalar::from(inputs.len() as u64);
+        for block in sponge_blocks(inputs.len(), self.rate()) {
+            for (j, i) in block.enumerate() {
+                state[j + 1] += inputs[i];
+            }
+            state = self.permute(&state);
+        }
+        state[1]
+    }
+
+    fn new_hash_constraint(&self, r1cs: &mut R1CS, inputs: &[&str], out: &str) {
+        let one = Scalar::one();
+        let mut state = vec![LinearCombination::zero(); self.width];
+        state[0] = LinearCombination::from_scalar(Scalar::from(inputs.len() as u64));
+        for (b, block) in sponge_blocks(inputs.len(), self.rate()).into_iter().enumerate() {
+            for (j, i) in block.enumerate() {
+                state[j + 1].add_term(inputs[i], one);
+            }
+            state = r1cs.poseidon_permutation_lc(self, &format!("{}_block[{}]", out, b), state);
+        }
+        r1cs.new_lc_constraint(&state[1].terms(), &[("one", one)], &[(out, one)]);
+    }
+
+    fn generate_witness_hash(&self, r1cs: &mut R1CS, inputs: &[Scalar], out: &str) -> Scalar {
+        let mut state = vec![Scalar::zero(); self.width];
+        state[0] = Scalar::from(inputs.len() as u64);
+        for (b, block) in sponge_blocks(inputs.len(), self.rate()).into_iter().enumerate() {
+            for (j, i) in block.enumerate() {
+                state[j + 1] += inputs[i];
+            }
+            state = r1cs.generate_witness_poseidon_permutation_lc(self, &format!("{}_block[{}]", out, b), &state);
+        }
+        r1cs.add_witness_var_assignment(out, state[1]);
+        state[1]
+    }
+}
+
+impl R1CS {
+    /*  Add constraints applying the Poseidon permutation to STATE and return the resulting
+        state. ARK and MDS steps are linear, so the state is carried as linear combinations
+        and only the S-boxes cost constraints (3 each: x^2, x^4 and x^5). */
+    fn poseidon_permutation_lc(&mut self, poseidon: &Poseidon, prefix: &str, state: Vec<LinearCombination>) -> Vec<LinearCombination> {
+        let one = Scalar::one();
+        let mut state = state;
+        for round in 0..poseidon.full_rounds + poseidon.partial_rounds {
+            for (j, x) in state.iter_mut().enumerate() {
+                x.add_term("one", poseidon.round_constants[round * poseidon.width + j]);
+            }
+            let sbox_count = if poseidon.is_full_round(round) { poseidon.width } else { 1 };
+            for (j, x) in state.iter_mut().enumerate().take(sbox_count) {
+                let x2 = format!("{}_poseidon_x2[{}][{}]", prefix, round, j);
+                let x4 = format!("{}_poseidon_x4[{}][{}]", prefix, round, j);
+                let x5 = format!("{}_poseidon_x5[{}][{}]", prefix, round, j);
+                self.new_lc_constraint(&x.terms(), &x.terms(), &[(&x2, one)]);
+                self.new_lc_constraint(&[(&x2, one)], &[(&x2, one)], &[(&x4, one)]);
+                self.new_lc_constraint(&[(&x4, one)], &x.terms(), &[(&x5, one)]);
+                *x = LinearCombination::from_var(&x5);
+            }
+            state = poseidon.mds.iter()
+                .map(|row| row.iter().zip(state.iter())
+                    .fold(LinearCombination::zero(), |acc, (m, x)| acc.add_scaled(x, *m)))
+                .collect();
+        }
+        state
+    }
+
+    fn generate_witness_poseidon_permutation_lc(&mut self, poseidon: &Poseidon, prefix: &str, state: &[Scalar]) -> Vec<Scalar> {
+        let mut state: Vec<Scalar> = state.to_vec();
+        for round in 0..poseidon.full_rounds + poseidon.partial_rounds {
+            for (x, c) in state.iter_mut().zip(poseidon.round_constants[round * poseidon.width..].iter()) {
+                *x += c;
+            }
+            let sbox_count = if poseidon.is_full_round(round) { poseidon.width } else { 1 };
+            for (j, x) in state.iter_mut().enumerate().take(sbox_count) {
+                let x2 = *x * *x;
+                let x4 = x2 * x2;
+                let x5 = x4 * *x;
+                self.add_witness_var_assignment(&format!("{}_poseidon_x2[{}][{}]", prefix, round, j), x2);
+                self.add_witness_var_assignment(&format!("{}_poseidon_x4[{}][{}]", prefix, round, j), x4);
+                self.add_witness_var_assignment(&format!("{}_poseidon_x5[{}][{}]", prefix, round, j), x5);
+                *x = x5;
+            }
+            state = poseidon.mds.iter()
+                .map(|row| row.iter().zip(state.iter()).map(|(m, x)| m * x).sum())
+                .collect();
+        }
+        state
+    }
+
+    /* Add constraints so that OUTPUTS is the Poseidon permutation of INPUTS. */
+    pub fn new_poseidon_permutation_constraint(&mut self, poseidon: &Poseidon, inputs: &[&str], outputs: &[&str]) {
+        assert!(inputs.len() == poseidon.width && outputs.len() == poseidon.width);
+        let one = Scalar::one();
+        let state = inputs.iter().map(|x| LinearCombination::from_var(x)).collect();
+        let state = self.poseidon_permutation_lc(poseidon, outputs[0], state);
+        for (x, out) in state.iter().zip(outputs.iter()) {
+            self.new_lc_constraint(&x.terms(), &[("one", one)], &[(out, one)]);
+        }
+    }
+
+    pub fn generate_witness_poseidon_permutation(&mut self, poseidon: &Poseidon, inputs: &[Scalar], outputs: &[&str]) -> Vec<Scalar> {
+        let state = self.generate_witness_poseidon_permutation_lc(poseidon, outputs[0], inputs);
+        for (x, out) in state.iter().zip(outputs.iter()) {
+            self.add_witness_var_assignment(out, *x);
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod poseidon_tests {
+    use curve25519_dalek::scalar::Scalar;
+    use crate::hash::HashGadget;
+    use crate::poseidon::{GrainLFSR, Poseidon};
+    use crate::r1cs::R1CS;
+
+    #[test]
+    fn grain_lfsr_output_is_canonical_test() {
+        let mut lfsr = GrainLFSR::new(253, 3, 8, 57);
+        for _ in 0..50 {
+            let x = lfsr.next_scalar();
+            assert!(Scalar::from_canonical_bytes(x.to_bytes()).is_some());
+        }
+    }
+
+    #[test]
+    fn poseidon_parameters_are_deterministic_test() {
+        let p1 = Poseidon::new();
+        let p2 = Poseidon::new();
+        assert!(p1.round_constants == p2.round_constants);
+        assert_eq!(p1.round_constants.len(), (8 + 57) * 3);
+        assert!(p1.round_constants[..3] != Poseidon::with_params(3, 8, 56).round_constants[..3]);
+    }
+
+    #[test]
+    fn poseidon_mds_is_invertible_test() {
+        let m = Poseidon::new().mds;
+        let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        assert_ne!(det, Scalar::zero());
+    }
+
+    #[test]
+    fn poseidon_permutation_gadget_test() {
+        let poseidon = Poseidon::new();
+        let inputs = [Scalar::from(1u32), Scalar::from(2u32), Scalar::from(3u32)];
+        let mut r1cs: R1CS = R1CS::new(&Vec::new());
+        r1cs.new_poseidon_permutation_constraint(&poseidon, &["s0", "s1", "s2"], &["t0", "t1", "t2"]);
+        r1cs.add_witness_var_assignment("s0", inputs[0]);
+        r1cs.add_witness_var_assignment("s1", inputs[1]);
+        r1cs.add_witness_var_assignment("s2", inputs[2]);
+        let outputs = r1cs.generate_witness_poseidon_permutation(&poseidon, &inputs, &["t0", "t1", "t2"]);
+        assert!(r1cs.is_satisfied());
+        assert!(outputs == poseidon.permute(&inputs));
+    }
+
+    #[test]
+    fn poseidon_hash_is_length_separated_test() {
+        let poseidon = Poseidon::new();
+        assert_ne!(poseidon.hash(&[Scalar::one()]), poseidon.hash(&[Scalar::one(), Scalar::zero()]));
+        assert_ne!(poseidon.hash(&[]), poseidon.hash(&[Scalar::zero()]));
+    }
+}
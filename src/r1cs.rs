@@ -17,6 +17,49 @@ pub struct R1CS {
     C: Vec<(usize, usize, [u8; 32])>
 }
 
+/*  A linear combination of variables, stored as (variable, coefficient) terms. Constants are
+    terms on the "one" variable. Gadgets use these to carry linear intermediate values (e.g. the
+    state of a hash permutation between S-boxes) without allocating a constraint for them. */
+#[derive(Clone, Debug)]
+pub struct LinearCombination {
+    terms: Vec<(String, Scalar)>
+}
+
+impl LinearCombination {
+    pub fn zero() -> LinearCombination {
+        LinearCombination { terms: Vec::new() }
+    }
+
+    pub fn from_var(var: &str) -> LinearCombination {
+        LinearCombination { terms: Vec::from([(var.to_string(), Scalar::one())]) }
+    }
+
+    pub fn from_scalar(value: Scalar) -> LinearCombination {
+        LinearCombination { terms: Vec::from([("one".to_string(), value)]) }
+    }
+
+    /* Add COEFF * VAR, merging with an existing term on VAR if there is one. */
+    pub fn add_term(&mut self, var: &str, coeff: Scalar) {
+        match self.terms.iter_mut().find(|(v, _)| v == var) {
+            Some((_, c)) => *c += coeff,
+            None => self.terms.push((var.to_string(), coeff))
+        }
+    }
+
+    /* Return SELF + COEFF * OTHER. */
+    pub fn add_scaled(&self, other: &LinearCombination, coeff: Scalar) -> LinearCombination {
+        let mut lc = self.clone();
+        for (var, c) in other.terms.iter() {
+            lc.add_term(var, coeff * c);
+        }
+        lc
+    }
+
+    pub fn terms(&self) -> Vec<(&str, Scalar)> {
+        self.terms.iter().map(|(v, c)| (v.as_str(), *c)).collect()
+    }
+}
+
 impl R1CS {
     pub fn new(inputs: &Vec<(&str, Scalar)>) -> R1CS {
         let mut r1cs = R1CS {
@@ -76,6 +119,15 @@ impl R1CS {
         (assignment_vars, assignment_inputs)
     }
 
+    /*  Whether the witness satisfies the constraints, for the gadget tests. It builds the
+        instance and the witness, so it should also only be called once. */
+    #[cfg(test)]
+    pub(crate) fn is_satisfied(&mut self) -> bool {
+        let (inst, _, _, _, _) = self.build_instance();
+        let (assignment_vars, assignment_inputs) = self.build_witness();
+        inst.is_sat(&assignment_vars, &assignment_inputs).unwrap_or(false)
+    }
+
     /* Return the next available index in the witness vector for a new variable. */
     fn get_new_var_index(&self) -> usize {
         self.variables.len()
@@ -191,6 +243,25 @@ impl R1CS {
         self.constraint_count += 1;
     }
 
+    /*  Add constraint that (sum of A) * (sum of B) == (sum of C), where A, B and C are lists
+    of (variable, coefficient) pairs. Every other constraint can be written this way, which lets
+    gadgets fold linear terms into a single row instead of spending one row per addition. */
+    pub fn new_lc_constraint(&mut self, a: &[(&str, Scalar)], b: &[(&str, Scalar)], c: &[(&str, Scalar)]) {
+        for (var, coeff) in a {
+            let var_ind = self.get_var_index(var);
+            self.A.push((self.constraint_count, var_ind, coeff.to_bytes()));
+        }
+        for (var, coeff) in b {
+            let var_ind = self.get_var_index(var);
+            self.B.push((self.constraint_count, var_ind, coeff.to_bytes()));
+        }
+        for (var, coeff) in c {
+            let var_ind = self.get_var_index(var);
+            self.C.push((self.constraint_count, var_ind, coeff.to_bytes()));
+        }
+        self.constraint_count += 1;
+    }
+
     /* creates N new variables of the form X_biti for i in [0, N). Each X_biti is constrained
        to be a bit. For all i, X_biti is constrained to be the i-th bit of X when X is a
        twos complement N bit number.*/
@@ -0,0 +1,235 @@
+extern crate curve25519_dalek;
+extern crate merlin;
+
+use curve25519_dalek::scalar::Scalar;
+use merlin::Transcript;
+use crate::bit_helpers::scalar_pow;
+use crate::hash::{cauchy_mds_matrix, sponge_blocks, HashGadget};
+use crate::r1cs::{LinearCombination, R1CS};
+
+/*  Default Rescue-Prime parameters: width 3 (rate 2, capacity 1) and S-box x^5. The round
+    count is the one given by the Rescue-Prime specification's get_number_of_rounds for
+    128 bit security: the Groebner basis bound is met at 9 rounds, and the specification adds
+    a 50% margin, giving ceil(1.5 * max(5, 9)) = 14. */
+pub const RESCUE_WIDTH: usize = 3;
+pub const RESCUE_ROUNDS: usize = 14;
+
+/* 1/5 mod (l - 1) in little endian, so that (x^5)^(1/5) == x for every scalar x. */
+const ALPHA_INV: [u8; 32] = [
+    0xc1, 0x18, 0x2d, 0x9e, 0xdc, 0xa1, 0xa4, 0x01, 0xe7, 0x2a, 0x2e, 0xc8, 0xb8, 0x62, 0x1f, 0xa6,
+    0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x99, 0x09,
+];
+
+/*  The Rescue-Prime permutation and a sponge built from it, over the curve25519 scalar field.
+    Each round applies x^5 to every element, the MDS matrix and round constants, then x^(1/5)
+    to every element, the MDS matrix and round constants again.
+
+    The 2 * rounds * width round constants are produced by a merlin transcript labelled
+    "Rescue-Prime curve25519 scalar field" (64 bytes per constant under the label
+    "round constant", reduced mod l), and the MDS matrix is cauchy_mds_matrix(width). Note
+    this differs from the SHAKE256 based constant generation of the reference implementation. */
+pub struct RescuePrime {
+    width: usize,
+    rounds: usize,
+    round_constants: Vec<Scalar>,
+    mds: Vec<Vec<Scalar>>
+}
+
+impl RescuePrime {
+    pub fn new() -> RescuePrime {
+        RescuePrime::with_params(RESCUE_WIDTH, RESCUE_ROUNDS)
+    }
+
+    pub fn with_params(width: usize, rounds: usize) -> RescuePrime {
+        assert!(width >= 2);
+        let mut transcript = Transcript::new(b"Rescue-Prime curve25519 scalar field");
+        transcript.append_u64(b"width", width as u64);
+        transcript.append_u64(b"rounds", rounds as u64);
+        let round_constants = (0..2 * rounds * width).map(|_| {
+            let mut buf = [0u8; 64];
+            transcript.challenge_bytes(b"round constant", &mut buf);
+            Scalar::from_bytes_mod_order_wide(&buf)
+        }).collect();
+        RescuePrime { width, rounds, round_constants, mds: cauchy_mds_matrix(width) }
+    }
+
+    pub fn rate(&self) -> usize {
+        self.width - 1
+    }
+
+    /* Multiply STATE by the MDS matrix and add the STEP-th set of round constants. */
+    fn linear_layer(&self, state: &[Scalar], step: usize) -> Vec<Scalar> {
+        self.mds.iter()
+            .zip(self.round_constants[step * self.width..].iter())
+            .map(|(row, c)| row.iter().zip(state.iter()).map(|(m, x)| m * x).sum::<Scalar>() + c)
+            .collect()
+    }
+
+    /* Apply the permutation to STATE, which must have WIDTH elements. */
+    pub fn permute(&self, state: &[Scalar]) -> Vec<Scalar> {
+        assert_eq!(state.len(), self.width);
+        let mut state: Vec<Scalar> = state.to_vec();
+        for round in 0..self.rounds {
+            for x in state.iter_mut() {
+                let x2 = *x * *x;
+                *x = x2 * x2 * *x;
+            }
+            state = self.linear_layer(&state, 2 * round);
+            for x in state.iter_mut() {
+                *x = scalar_pow(*x, ALPHA_INV);
+            }
+            state = self.linear_layer(&state, 2 * round + 1);
+        }
+        state
+    }
+}
+
+impl Default for RescuePrime {
+    fn default() -> RescuePrime {
+        RescuePrime::new()
+    }
+}
+
+impl HashGadget for RescuePrime {
+    fn name(&self) -> &'static str {
+        "Rescue-Prime"
+    }
+
+    /* Same sponge construction as Poseidon: capacity element state[0] holds the input length. */
+    fn hash(&self, inputs: &[Scalar]) -> Scalar {
+        let mut state = vec![Scalar::zero(); self.width];
+        state[0] = Scalar::from(inputs.len() as u64);
+        for block in sponge_blocks(inputs.len(), self.rate()) {
+            for (j, i) in block.enumerate() {
+                state[j + 1] += inputs[i];
+            }
+            state = self.permute(&state);
+        }
+        state[1]
+    }
+
+    fn new_hash_constraint(&self, r1cs: &mut R1CS, inputs: &[&str], out: &str) {
+        let one = Scalar::one();
+        let mut state = vec![LinearCombination::zero(); self.width];
+        state[0] = LinearCombination::from_scalar(Scalar::from(inputs.len() as u64));
+        for (b, block) in sponge_blocks(inputs.len(), self.rate()).into_iter().enumerate() {
+            for (j, i) in block.enumerate() {
+                state[j + 1].add_term(inputs[i], one);
+            }
+            state = r1cs.rescue_permutation_lc(self, &format!("{}_block[{}]", out, b), state);
+        }
+        r1cs.new_lc_constraint(&state[1].terms(), &[("one", one)], &[(out, one)]);
+    }
+
+    fn generate_witness_hash(&self, r1cs: &mut R1CS, inputs: &[Scalar], out: &str) -> Scalar {
+        let mut state = vec![Scalar::zero(); self.width];
+        state[0] = Scalar::from(inputs.len() as u64);
+        for (b, block) in sponge_blocks(inputs.len(), self.rate()).into_iter().enumerate() {
+            for (j, i) in block.enumerate() {
+                state[j + 1] += inputs[i];
+            }
+            state = r1cs.generate_witness_rescue_permutation_lc(self, &format!("{}_block[{}]", out, b), &state);
+        }
+        r1cs.add_witness_var_assignment(out, state[1]);
+        state[1]
+    }
+}
+
+impl R1CS {
+    /*  Add constraints applying the Rescue-Prime permutation to STATE and return the new state.
+        The linear layers are folded into linear combinations. Both S-boxes cost 3 constraints:
+        y = x^5 via x^2, x^4, x^4 * x, and y = x^(1/5) by checking y^2, y^4, y^4 * y == x. */
+    fn rescue_permutation_lc(&mut self, rescue: &RescuePrime, prefix: &str, state: Vec<LinearCombination>) -> Vec<LinearCombination> {
+        let one = Scalar::one();
+        let linear_layer = |state: &[LinearCombination], step: usize| -> Vec<LinearCombination> {
+            rescue.mds.iter()
+                .zip(rescue.round_constants[step * rescue.width..].iter())
+                .map(|(row, c)| row.iter().zip(state.iter())
+                    .fold(LinearCombination::from_scalar(*c), |acc, (m, x)| acc.add_scaled(x, *m)))
+                .collect()
+        };
+
+        let mut state = state;
+        for round in 0..rescue.rounds {
+            for (j, x) in state.iter_mut().enumerate() {
+                let x2 = format!("{}_rescue_x2[{}][{}]", prefix, round, j);
+                let x4 = format!("{}_rescue_x4[{}][{}]", prefix, round, j);
+                let x5 = format!("{}_rescue_x5[{}][{}]", prefix, round, j);
+                self.new_lc_constraint(&x.terms(), &x.terms(), &[(&x2, one)]);
+                self.new_lc_constraint(&[(&x2, one)], &[(&x2, one)], &[(&x4, one)]);
+                self.new_lc_constraint(&[(&x4, one)], &x.terms(), &[(&x5, one)]);
+                *x = LinearCombination::from_var(&x5);
+            }
+            state = linear_layer(&state, 2 * round);
+            for (j, x) in state.iter_mut().enumerate() {
+                let y = format!("{}_rescue_y[{}][{}]", prefix, round, j);
+                let y2 = format!("{}_rescue_y2[{}][{}]", prefix, round, j);
+                let y4 = format!("{}_rescue_y4[{}][{}]", prefix, round, j);
+                self.new_lc_constraint(&[(&y, one)], &[(&y, one)], &[(&y2, one)]);
+                self.new_lc_constraint(&[(&y2, one)], &[(&y2, one)], &[(&y4, one)]);
+                self.new_lc_constraint(&[(&y4, one)], &[(&y, one)], &x.terms());
+                *x = LinearCombination::from_var(&y);
+            }
+            state = linear_layer(&state, 2 * round + 1);
+        }
+        state
+    }
+
+    fn generate_witness_rescue_permutation_lc(&mut self, rescue: &RescuePrime, prefix: &str, state: &[Scalar]) -> Vec<Scalar> {
+        let mut state: Vec<Scalar> = state.to_vec();
+        for round in 0..rescue.rounds {
+            for (j, x) in state.iter_mut().enumerate() {
+                let x2 = *x * *x;
+                let x4 = x2 * x2;
+                let x5 = x4 * *x;
+                self.add_witness_var_assignment(&format!("{}_rescue_x2[{}][{}]", prefix, round, j), x2);
+                self.add_witness_var_assignment(&format!("{}_rescue_x4[{}][{}]", prefix, round, j), x4);
+                self.add_witness_var_assignment(&format!("{}_rescue_x5[{}][{}]", prefix, round, j), x5);
+                *x = x5;
+            }
+            state = rescue.linear_layer(&state, 2 * round);
+            for (j, x) in state.iter_mut().enumerate() {
+                let y = scalar_pow(*x, ALPHA_INV);
+                let y2 = y * y;
+                let y4 = y2 * y2;
+                self.add_witness_var_assignment(&format!("{}_rescue_y[{}][{}]", prefix, round, j), y);
+                self.add_witness_var_assignment(&format!("{}_rescue_y2[{}][{}]", prefix, round, j), y2);
+                self.add_witness_var_assignment(&format!("{}_rescue_y4[{}][{}]", prefix, round, j), y4);
+                *x = y;
+            }
+            state = rescue.linear_layer(&state, 2 * round + 1);
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod rescue_tests {
+    use curve25519_dalek::scalar::Scalar;
+    use crate::bit_helpers::scalar_pow;
+    use crate::hash::HashGadget;
+    use crate::rescue::{RescuePrime, ALPHA_INV};
+
+    #[test]
+    fn rescue_alpha_inverse_test() {
+        for x in [Scalar::zero(), Scalar::one(), Scalar::from(7u32), -Scalar::from(12345u32)] {
+            let x2 = x * x;
+            assert_eq!(scalar_pow(x2 * x2 * x, ALPHA_INV), x);
+        }
+    }
+
+    #[test]
+    fn rescue_parameters_are_deterministic_test() {
+        let r1 = RescuePrime::new();
+        let r2 = RescuePrime::new();
+        assert!(r1.round_constants == r2.round_constants);
+        assert_eq!(r1.round_constants.len(), 2 * 14 * 3);
+    }
+
+    #[test]
+    fn rescue_hash_is_length_separated_test() {
+        let rescue = RescuePrime::new();
+        assert_ne!(rescue.hash(&[Scalar::one()]), rescue.hash(&[Scalar::one(), Scalar::zero()]));
+        assert_ne!(rescue.hash(&[]), rescue.hash(&[Scalar::zero()]));
+    }
+}